@@ -10,9 +10,15 @@ use std::{
     process::{exit, Command},
     sync::Arc,
 };
-use storage::{CloudStorage, GCSStorage, S3Storage};
+use storage::{
+    AzureStorage, CloudStorage, GCSStorage, GcsAuthOptions, ObjectVersion, S3Storage, UploadOutcome,
+};
 use tempfile::NamedTempFile;
-use tokio::{sync::mpsc, task, time::{Duration, Instant}};
+use tokio::{
+    sync::{mpsc, Mutex},
+    task,
+    time::{Duration, Instant},
+};
 
 /// Cloud Storage Editor Utility
 #[derive(Parser, Debug)]
@@ -26,7 +32,7 @@ use tokio::{sync::mpsc, task, time::{Duration, Instant}};
     .args(&["uri", "bucket"])
 ))]
 struct Cli {
-    /// Cloud storage provider (s3 for AWS S3, gcs for Google Cloud Storage)
+    /// Cloud storage provider (s3 for AWS S3, gcs for Google Cloud Storage, azure for Azure Blob Storage)
     #[arg(short, long, default_value = "s3")]
     storage: String,
 
@@ -53,6 +59,71 @@ struct Cli {
     /// Debounce writes interval (optional, defaults to 500ms)
     #[arg(long, short)]
     debounce: Option<u64>,
+
+    /// Part size in MB used for chunked multipart/resumable uploads (optional, defaults to 5 MB)
+    #[arg(long, value_parser = parse_positive_usize)]
+    part_size: Option<usize>,
+
+    /// Path to a GCS service-account JSON key (only used for --storage gcs)
+    #[arg(long)]
+    gcs_service_account: Option<String>,
+
+    /// Use anonymous, unauthenticated access to GCS (only used for --storage gcs)
+    #[arg(long)]
+    gcs_anonymous: bool,
+
+    /// GCP project id (only used for --storage gcs)
+    #[arg(long)]
+    gcs_project: Option<String>,
+
+    /// Custom or emulated storage endpoint (only used for --storage gcs)
+    #[arg(long)]
+    endpoint: Option<String>,
+
+    /// Window size in MB used for ranged/chunked downloads (optional, defaults to 8 MB; only used for --storage gcs)
+    #[arg(long, value_parser = parse_positive_usize)]
+    window_size: Option<usize>,
+
+    /// If set, unconditionally upload the current temp file on this fixed interval
+    /// (in seconds), regardless of debounce state, as a crash-safety net
+    #[arg(long, value_parser = parse_positive_u64)]
+    snapshot_interval: Option<u64>,
+
+    /// Maximum number of attempts for a sync before giving up (optional, defaults to 5)
+    #[arg(long, default_value_t = 5, value_parser = parse_positive_u32)]
+    max_sync_attempts: u32,
+}
+
+/// Value parser shared by size/count flags that must be strictly positive
+/// (a `0` is either meaningless or crashes the code that consumes it).
+fn parse_positive_usize(s: &str) -> std::result::Result<usize, String> {
+    let value: usize = s.parse().map_err(|e| format!("`{}`: {}", s, e))?;
+    if value == 0 {
+        return Err("must be greater than 0".to_string());
+    }
+    Ok(value)
+}
+
+/// Same as `parse_positive_usize`, for flags expressed in `u64` (e.g.
+/// durations in seconds). `tokio::time::interval` panics on a zero period, so
+/// `--snapshot-interval` must be rejected here rather than at runtime.
+fn parse_positive_u64(s: &str) -> std::result::Result<u64, String> {
+    let value: u64 = s.parse().map_err(|e| format!("`{}`: {}", s, e))?;
+    if value == 0 {
+        return Err("must be greater than 0".to_string());
+    }
+    Ok(value)
+}
+
+/// Same as `parse_positive_usize`, for flags expressed in `u32`. `sync_file`'s
+/// `1..=max_attempts` retry loop silently never runs when `max_attempts` is
+/// `0`, so `--max-sync-attempts` must be rejected here rather than at runtime.
+fn parse_positive_u32(s: &str) -> std::result::Result<u32, String> {
+    let value: u32 = s.parse().map_err(|e| format!("`{}`: {}", s, e))?;
+    if value == 0 {
+        return Err("must be greater than 0".to_string());
+    }
+    Ok(value)
 }
 
 #[tokio::main]
@@ -63,9 +134,21 @@ async fn main() -> Result<()> {
     let (bucket, key) =
         uri::parse_uri(&cli.uri)?.unwrap_or_else(|| (cli.bucket.unwrap(), cli.key.unwrap()));
 
+    let part_size_bytes = cli.part_size.map(|mb| mb * 1024 * 1024);
+    let window_size_bytes = cli.window_size.map(|mb| mb * 1024 * 1024);
+
     let storage_client = match cli.storage.as_str() {
-        "s3" => CloudStorage::S3(S3Storage::new(cli.region).await),
-        "gcs" => CloudStorage::GCS(GCSStorage::new(None).await?),
+        "s3" => CloudStorage::S3(S3Storage::new(cli.region, part_size_bytes).await),
+        "gcs" => {
+            let gcs_auth = GcsAuthOptions {
+                service_account_path: cli.gcs_service_account,
+                anonymous: cli.gcs_anonymous,
+                project_id: cli.gcs_project,
+                endpoint: cli.endpoint,
+            };
+            CloudStorage::GCS(GCSStorage::new(part_size_bytes, window_size_bytes, gcs_auth).await?)
+        }
+        "azure" => CloudStorage::Azure(AzureStorage::new(&bucket).await?),
         _ => {
             return Err(anyhow::anyhow!(
                 "Unsupported storage provider: {}",
@@ -75,8 +158,18 @@ async fn main() -> Result<()> {
     };
 
     let debounce_duration = Duration::from_millis(cli.debounce.unwrap_or(500));
+    let snapshot_interval = cli.snapshot_interval.map(Duration::from_secs);
 
-    cloud_edit(storage_client, &bucket, &key, cli.file_path, debounce_duration).await?;
+    cloud_edit(
+        storage_client,
+        &bucket,
+        &key,
+        cli.file_path,
+        debounce_duration,
+        snapshot_interval,
+        cli.max_sync_attempts,
+    )
+    .await?;
 
     Ok(())
 }
@@ -87,6 +180,8 @@ async fn cloud_edit(
     key: &str,
     file_path: Option<String>,
     debounce_duration: Duration,
+    snapshot_interval: Option<Duration>,
+    max_sync_attempts: u32,
 ) -> Result<()> {
     let client = Arc::new(client);
 
@@ -97,8 +192,10 @@ async fn cloud_edit(
             .unwrap_or_else(|| NamedTempFile::new().unwrap().into_temp_path().to_path_buf()),
     );
 
-    // Download file from cloud storage to temporary location
-    client.download_file(bucket, key, &temp_path).await?;
+    // Download file from cloud storage to temporary location, remembering the
+    // remote version it came from so syncs can detect if it moves underneath us.
+    let version = client.download_file(bucket, key, &temp_path).await?;
+    let version = Arc::new(Mutex::new(version));
 
     // Channel to signal file watcher termination
     let (stop_tx, stop_rx) = mpsc::channel(1);
@@ -111,6 +208,9 @@ async fn cloud_edit(
         key.to_string(),
         stop_rx,
         debounce_duration,
+        version,
+        snapshot_interval,
+        max_sync_attempts,
     ));
 
     // Open the file in the user's preferred editor
@@ -125,6 +225,129 @@ async fn cloud_edit(
     Ok(())
 }
 
+/// Initial delay before the first retry of a failed sync; doubles on each
+/// subsequent attempt.
+const INITIAL_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Uploads `file_path`, conditioned on the remote object still being at
+/// `version`. On a conflict, downloads the remote object that won the race to
+/// a sibling `.remote` file and leaves `version` untouched, so later syncs in
+/// this session are refused as well: `version` is never moved on to the remote
+/// revision, and saving the file again does not clear the conflict. Any other
+/// upload error (a network blip, a 5xx response or anything else) is retried
+/// with exponential backoff, for at most `max_attempts` attempts in all,
+/// before giving up.
+async fn sync_file(
+    storage_client: &CloudStorage,
+    bucket: &str,
+    key: &str,
+    file_path: &Path,
+    version: &Mutex<ObjectVersion>,
+    max_attempts: u32,
+) {
+    let expected_version = version.lock().await.clone();
+    let mut backoff = INITIAL_RETRY_BACKOFF;
+
+    for attempt in 1..=max_attempts {
+        match storage_client
+            .upload_file(bucket, key, file_path, &expected_version)
+            .await
+        {
+            Ok(UploadOutcome::Uploaded(new_version)) => {
+                *version.lock().await = new_version;
+                return;
+            }
+            Ok(UploadOutcome::Conflict) => {
+                let remote_path = remote_conflict_path(file_path);
+                eprintln!(
+                    "Conflict: the remote object has changed since it was last downloaded; refusing to overwrite it."
+                );
+                match storage_client
+                    .download_file(bucket, key, &remote_path)
+                    .await
+                {
+                    Ok(_) => eprintln!(
+                        "Downloaded the current remote version to {} — later saves in this session will be refused as well.",
+                        remote_path.display()
+                    ),
+                    Err(e) => eprintln!("Failed to download the conflicting remote version: {}", e),
+                }
+                return;
+            }
+            Err(e) if attempt < max_attempts => {
+                eprintln!(
+                    "Failed to sync changes (attempt {}/{}): {}. Retrying in {:?}...",
+                    attempt, max_attempts, e, backoff
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            Err(e) => {
+                eprintln!("Failed to sync changes after {} attempts: {}", attempt, e);
+            }
+        }
+    }
+}
+
+/// Path of the sibling file used to stash the remote version during a conflict.
+fn remote_conflict_path(file_path: &Path) -> PathBuf {
+    let mut remote_path = file_path.as_os_str().to_owned();
+    remote_path.push(".remote");
+    PathBuf::from(remote_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_conflict_path_appends_remote_suffix() {
+        let path = remote_conflict_path(Path::new("/tmp/eo-edit-abc123"));
+        assert_eq!(path, PathBuf::from("/tmp/eo-edit-abc123.remote"));
+    }
+
+    #[test]
+    fn test_remote_conflict_path_preserves_existing_extension() {
+        let path = remote_conflict_path(Path::new("/tmp/notes.txt"));
+        assert_eq!(path, PathBuf::from("/tmp/notes.txt.remote"));
+    }
+
+    #[test]
+    fn test_parse_positive_usize_accepts_positive_value() {
+        assert_eq!(parse_positive_usize("5"), Ok(5));
+    }
+
+    #[test]
+    fn test_parse_positive_usize_rejects_zero() {
+        assert!(parse_positive_usize("0").is_err());
+    }
+
+    #[test]
+    fn test_parse_positive_usize_rejects_non_numeric() {
+        assert!(parse_positive_usize("not-a-number").is_err());
+    }
+
+    #[test]
+    fn test_parse_positive_u64_accepts_positive_value() {
+        assert_eq!(parse_positive_u64("5"), Ok(5));
+    }
+
+    #[test]
+    fn test_parse_positive_u64_rejects_zero() {
+        assert!(parse_positive_u64("0").is_err());
+    }
+
+    #[test]
+    fn test_parse_positive_u32_accepts_positive_value() {
+        assert_eq!(parse_positive_u32("5"), Ok(5));
+    }
+
+    #[test]
+    fn test_parse_positive_u32_rejects_zero() {
+        assert!(parse_positive_u32("0").is_err());
+    }
+}
+
 fn edit_file(file_path: &Path) -> Result<()> {
     let editor = env::var("EDITOR").unwrap_or_else(|_| "vim".to_string());
     let status = Command::new(editor).arg(file_path).status()?;
@@ -144,10 +367,14 @@ async fn watch_and_sync_file(
     key: String,
     mut stop_rx: mpsc::Receiver<()>,
     debounce_duration: Duration,
+    version: Arc<Mutex<ObjectVersion>>,
+    snapshot_interval: Option<Duration>,
+    max_sync_attempts: u32,
 ) -> Result<()> {
     let (tx, mut rx) = mpsc::unbounded_channel();
     let mut last_event = Instant::now();
     let mut debounce_timer = None;
+    let mut snapshot_ticker = snapshot_interval.map(tokio::time::interval);
 
     let mut watcher = RecommendedWatcher::new(
         move |res| {
@@ -166,7 +393,7 @@ async fn watch_and_sync_file(
         },
         notify::Config::default(),
     )?;
-    
+
     watcher.watch(&file_path, RecursiveMode::NonRecursive)?;
 
     loop {
@@ -188,12 +415,20 @@ async fn watch_and_sync_file(
                 }
             }, if debounce_timer.is_some() => {
                 if last_event.elapsed() >= debounce_duration {
-                    if let Err(e) = storage_client.upload_file(&bucket, &key, &file_path).await {
-                        eprintln!("Failed to sync changes to S3: {}", e);
-                    }
+                    sync_file(&storage_client, &bucket, &key, &file_path, &version, max_sync_attempts).await;
                 }
                 debounce_timer = None;
             }
+            // Unconditional periodic snapshot, independent of debounce state -
+            // a safety net against a crash during a long idle-then-quit window.
+            _ = async {
+                match &mut snapshot_ticker {
+                    Some(ticker) => ticker.tick().await,
+                    None => std::future::pending().await,
+                }
+            }, if snapshot_ticker.is_some() => {
+                sync_file(&storage_client, &bucket, &key, &file_path, &version, max_sync_attempts).await;
+            }
             // Stop signal
             _ = stop_rx.recv() => {
                 break;
@@ -1,40 +1,153 @@
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
 use aws_sdk_s3::Client as S3Client;
+use azure_core::error::HttpError;
+use azure_storage::prelude::*;
+use azure_storage_blobs::prelude::*;
+use futures::StreamExt;
+use google_cloud_auth::credentials::CredentialsFile;
 use google_cloud_storage::client::{Client as GCSClient, ClientConfig as GCSClientConfig};
 use google_cloud_storage::http::objects::download::Range;
 use google_cloud_storage::http::objects::get::GetObjectRequest;
 use google_cloud_storage::http::objects::upload::{Media, UploadObjectRequest, UploadType};
+use google_cloud_storage::http::resumable_upload_client::{ChunkSize, ResumableUploadClient};
 
 use anyhow::{Ok, Result};
+use std::env;
 use std::path::Path;
 use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Files at or below this size are uploaded in a single request; larger
+/// files switch to the chunked multipart/resumable upload path.
+const MULTIPART_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// Default size of each chunk when uploading in multipart/resumable mode.
+const DEFAULT_PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// Default size of each ranged-read window when streaming a GCS download.
+const DEFAULT_WINDOW_SIZE: usize = 8 * 1024 * 1024;
+
+/// Identifies the specific remote revision a local edit was based on, so a
+/// sync can be made conditional on nothing else having written in the meantime.
+#[derive(Clone, Debug)]
+pub enum ObjectVersion {
+    S3 {
+        etag: String,
+    },
+    GCS {
+        generation: i64,
+        metageneration: i64,
+    },
+    Azure {
+        etag: String,
+    },
+}
+
+impl ObjectVersion {
+    fn s3_etag(&self) -> Result<&str> {
+        match self {
+            ObjectVersion::S3 { etag } => Ok(etag),
+            _ => Err(anyhow::anyhow!("expected an S3 object version")),
+        }
+    }
+
+    fn gcs_generation(&self) -> Result<i64> {
+        match self {
+            ObjectVersion::GCS { generation, .. } => Ok(*generation),
+            _ => Err(anyhow::anyhow!("expected a GCS object version")),
+        }
+    }
+
+    fn azure_etag(&self) -> Result<&str> {
+        match self {
+            ObjectVersion::Azure { etag } => Ok(etag),
+            _ => Err(anyhow::anyhow!("expected an Azure object version")),
+        }
+    }
+}
+
+/// Result of a conditional upload: either it succeeded and produced a new
+/// version, or the remote object had already moved and the upload was skipped.
+pub enum UploadOutcome {
+    Uploaded(ObjectVersion),
+    Conflict,
+}
+
+/// Returns true if an S3 error is a 412 Precondition Failed, i.e. the If-Match
+/// condition on the request no longer matched the object.
+fn s3_is_conflict<E>(err: &aws_sdk_s3::error::SdkError<E>) -> bool {
+    err.raw_response()
+        .map(|r| r.status().as_u16() == 412)
+        .unwrap_or(false)
+}
+
+/// Returns true if a GCS error is a 412 Precondition Failed, i.e. the
+/// ifGenerationMatch condition on the request no longer matched the object.
+fn gcs_is_conflict(err: &google_cloud_storage::http::Error) -> bool {
+    matches!(err, google_cloud_storage::http::Error::Response(resp) if resp.code == 412)
+}
+
+/// Returns true if an Azure error is a 412 Precondition Failed, i.e. the
+/// If-Match condition on the request no longer matched the blob.
+fn azure_is_conflict(err: &azure_core::Error) -> bool {
+    err.as_http_error()
+        .map(|e| e.status() == azure_core::StatusCode::PreconditionFailed)
+        .unwrap_or(false)
+}
 
 pub enum CloudStorage {
     S3(S3Storage),
     GCS(GCSStorage),
+    Azure(AzureStorage),
 }
 
 impl CloudStorage {
-    pub async fn download_file(&self, bucket: &str, key: &str, local_path: &Path) -> Result<()> {
+    pub async fn download_file(
+        &self,
+        bucket: &str,
+        key: &str,
+        local_path: &Path,
+    ) -> Result<ObjectVersion> {
         match self {
             CloudStorage::S3(s3) => s3.download_file(bucket, key, local_path).await,
             CloudStorage::GCS(gcs) => gcs.download_file(bucket, key, local_path).await,
+            CloudStorage::Azure(azure) => azure.download_file(bucket, key, local_path).await,
         }
     }
 
-    pub async fn upload_file(&self, bucket: &str, key: &str, local_path: &Path) -> Result<()> {
+    pub async fn upload_file(
+        &self,
+        bucket: &str,
+        key: &str,
+        local_path: &Path,
+        expected_version: &ObjectVersion,
+    ) -> Result<UploadOutcome> {
         match self {
-            CloudStorage::S3(s3) => s3.upload_file(bucket, key, local_path).await,
-            CloudStorage::GCS(gcs) => gcs.upload_file(bucket, key, local_path).await,
+            CloudStorage::S3(s3) => {
+                s3.upload_file(bucket, key, local_path, expected_version)
+                    .await
+            }
+            CloudStorage::GCS(gcs) => {
+                gcs.upload_file(bucket, key, local_path, expected_version)
+                    .await
+            }
+            CloudStorage::Azure(azure) => {
+                azure
+                    .upload_file(bucket, key, local_path, expected_version)
+                    .await
+            }
         }
     }
 }
 
 pub struct S3Storage {
     client: S3Client,
+    part_size: usize,
 }
 
 impl S3Storage {
-    pub async fn new(region: Option<String>) -> Self {
+    pub async fn new(region: Option<String>, part_size: Option<usize>) -> Self {
         let config = aws_config::from_env()
             .region(region.map(aws_sdk_s3::Region::new))
             .load()
@@ -42,91 +155,649 @@ impl S3Storage {
 
         Self {
             client: S3Client::new(&config),
+            part_size: part_size.unwrap_or(DEFAULT_PART_SIZE),
         }
     }
 
-    pub async fn download_file(&self, bucket: &str, key: &str, local_path: &Path) -> Result<()> {
-        let content = self
+    pub async fn download_file(
+        &self,
+        bucket: &str,
+        key: &str,
+        local_path: &Path,
+    ) -> Result<ObjectVersion> {
+        let mut output = self
             .client
             .get_object()
             .bucket(bucket)
             .key(key)
             .send()
-            .await?
-            .body
-            .collect()
-            .await?
-            .into_bytes();
-        fs::write(local_path, &content).await?;
-        Ok(())
+            .await?;
+        let etag = output
+            .e_tag()
+            .ok_or_else(|| anyhow::anyhow!("get_object returned no ETag"))?
+            .to_string();
+
+        // Truncate/create fresh so re-downloads don't leave stale tail bytes.
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(local_path)
+            .await?;
+
+        while let Some(chunk) = output.body.try_next().await? {
+            file.write_all(&chunk).await?;
+        }
+
+        Ok(ObjectVersion::S3 { etag })
     }
 
-    pub async fn upload_file(&self, bucket: &str, key: &str, local_path: &Path) -> Result<()> {
-        // Read the modified content from the local file
-        let content = fs::read(local_path).await?;
+    pub async fn upload_file(
+        &self,
+        bucket: &str,
+        key: &str,
+        local_path: &Path,
+        expected_version: &ObjectVersion,
+    ) -> Result<UploadOutcome> {
+        let etag = expected_version.s3_etag()?;
+        let file_size = fs::metadata(local_path).await?.len();
+
+        if file_size <= MULTIPART_THRESHOLD {
+            let content = fs::read(local_path).await?;
 
-        // Upload the file back to S3
-        self.client
-            .put_object()
+            match self
+                .client
+                .put_object()
+                .bucket(bucket)
+                .key(key)
+                .if_match(etag)
+                .body(content.into())
+                .send()
+                .await
+            {
+                std::result::Result::Ok(output) => {
+                    let new_etag = output
+                        .e_tag()
+                        .ok_or_else(|| anyhow::anyhow!("put_object returned no ETag"))?
+                        .to_string();
+                    Ok(UploadOutcome::Uploaded(ObjectVersion::S3 {
+                        etag: new_etag,
+                    }))
+                }
+                Err(e) if s3_is_conflict(&e) => Ok(UploadOutcome::Conflict),
+                Err(e) => Err(e.into()),
+            }
+        } else {
+            self.upload_file_multipart(bucket, key, local_path, etag)
+                .await
+        }
+    }
+
+    async fn upload_file_multipart(
+        &self,
+        bucket: &str,
+        key: &str,
+        local_path: &Path,
+        expected_etag: &str,
+    ) -> Result<UploadOutcome> {
+        let upload_id = self
+            .client
+            .create_multipart_upload()
             .bucket(bucket)
             .key(key)
-            .body(content.into())
             .send()
-            .await?;
+            .await?
+            .upload_id()
+            .ok_or_else(|| anyhow::anyhow!("create_multipart_upload returned no upload id"))?
+            .to_string();
+
+        let parts = match self.upload_parts(bucket, key, local_path, &upload_id).await {
+            std::result::Result::Ok(parts) => parts,
+            Err(e) => {
+                // Clean up the in-progress upload so no orphan parts are billed.
+                // The abort failing doesn't change the fact that the real
+                // upload failed, so report that and just log the abort error.
+                if let Err(abort_err) = self
+                    .client
+                    .abort_multipart_upload()
+                    .bucket(bucket)
+                    .key(key)
+                    .upload_id(&upload_id)
+                    .send()
+                    .await
+                {
+                    eprintln!(
+                        "Failed to abort multipart upload {} after a failed part upload: {}",
+                        upload_id, abort_err
+                    );
+                }
+                return Err(e);
+            }
+        };
 
-        Ok(())
+        match self
+            .client
+            .complete_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(&upload_id)
+            .if_match(expected_etag)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(parts))
+                    .build(),
+            )
+            .send()
+            .await
+        {
+            std::result::Result::Ok(output) => {
+                let new_etag = output
+                    .e_tag()
+                    .ok_or_else(|| anyhow::anyhow!("complete_multipart_upload returned no ETag"))?
+                    .to_string();
+                Ok(UploadOutcome::Uploaded(ObjectVersion::S3 {
+                    etag: new_etag,
+                }))
+            }
+            Err(e) => {
+                // The upload id is no longer needed either way; abort it so no
+                // orphan parts are billed. An abort failure here is secondary
+                // to the original error, so log it rather than letting it
+                // shadow the reason the upload actually failed.
+                if let Err(abort_err) = self
+                    .client
+                    .abort_multipart_upload()
+                    .bucket(bucket)
+                    .key(key)
+                    .upload_id(&upload_id)
+                    .send()
+                    .await
+                {
+                    eprintln!(
+                        "Failed to abort multipart upload {} after completion failed: {}",
+                        upload_id, abort_err
+                    );
+                }
+
+                if s3_is_conflict(&e) {
+                    Ok(UploadOutcome::Conflict)
+                } else {
+                    Err(e.into())
+                }
+            }
+        }
     }
+
+    async fn upload_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        local_path: &Path,
+        upload_id: &str,
+    ) -> Result<Vec<CompletedPart>> {
+        let mut file = fs::File::open(local_path).await?;
+        let mut parts = Vec::new();
+        let mut part_number = 1i32;
+        let mut buf = vec![0u8; self.part_size];
+
+        loop {
+            let bytes_read = read_up_to(&mut file, &mut buf).await?;
+            if bytes_read == 0 {
+                break;
+            }
+
+            let part = self
+                .client
+                .upload_part()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(ByteStream::from(buf[..bytes_read].to_vec()))
+                .send()
+                .await?;
+
+            let e_tag = part
+                .e_tag()
+                .ok_or_else(|| anyhow::anyhow!("upload_part returned no ETag"))?
+                .to_string();
+
+            parts.push(
+                CompletedPart::builder()
+                    .e_tag(e_tag)
+                    .part_number(part_number)
+                    .build(),
+            );
+
+            part_number += 1;
+
+            if bytes_read < buf.len() {
+                break;
+            }
+        }
+
+        Ok(parts)
+    }
+}
+
+/// Fills `buf` by reading repeatedly until it is full or the file is exhausted,
+/// returning the number of bytes actually read.
+async fn read_up_to(file: &mut fs::File, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let n = file.read(&mut buf[filled..]).await?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
+
+/// Authentication and endpoint configuration for a `GCSStorage` client.
+#[derive(Default)]
+pub struct GcsAuthOptions {
+    /// Path to a service-account JSON key file.
+    pub service_account_path: Option<String>,
+    /// Use anonymous (unauthenticated) access, for public buckets.
+    pub anonymous: bool,
+    /// Explicit GCP project id, overriding whatever auth would infer.
+    pub project_id: Option<String>,
+    /// Custom/emulated storage endpoint, e.g. `http://localhost:4443`.
+    pub endpoint: Option<String>,
 }
 
 pub struct GCSStorage {
     client: GCSClient,
+    part_size: usize,
+    window_size: usize,
 }
 
 impl GCSStorage {
-    pub async fn new(region: Option<String>) -> Self {
-        // TODO: use region in client config.
+    pub async fn new(
+        part_size: Option<usize>,
+        window_size: Option<usize>,
+        auth: GcsAuthOptions,
+    ) -> Result<Self> {
+        let mut config = GCSClientConfig::default();
 
-        // TODO: bubble up the error properly.
-        let config = GCSClientConfig::default()
-            .with_auth()
-            .await
-            .expect("Failed to create GCS client");
+        if let Some(project_id) = auth.project_id {
+            config.project_id = Some(project_id);
+        }
+        if let Some(endpoint) = auth.endpoint {
+            config.storage_endpoint = endpoint;
+        }
 
-        Self {
+        let config = if auth.anonymous {
+            config.anonymous()
+        } else if let Some(path) = auth.service_account_path {
+            let credentials_file = CredentialsFile::new_from_file(path).await?;
+            config.with_credentials(credentials_file).await?
+        } else {
+            config.with_auth().await?
+        };
+
+        Ok(Self {
             client: GCSClient::new(config),
-        }
+            part_size: part_size.unwrap_or(DEFAULT_PART_SIZE),
+            window_size: window_size.unwrap_or(DEFAULT_WINDOW_SIZE),
+        })
     }
 
-    pub async fn download_file(&self, bucket: &str, key: &str, local_path: &Path) -> Result<()> {
+    pub async fn download_file(
+        &self,
+        bucket: &str,
+        key: &str,
+        local_path: &Path,
+    ) -> Result<ObjectVersion> {
         let request = GetObjectRequest {
             bucket: bucket.to_string(),
             object: key.to_string(),
             ..Default::default()
         };
 
-        let data = self
-            .client
-            .download_object(&request, &Range::default())
+        // Fetch the object's metadata (for its generation/metageneration and
+        // size) up front; the download API only returns bytes.
+        let metadata = self.client.get_object(&request).await?;
+        let total_size = metadata.size;
+
+        // Pin every ranged read below to this exact generation, so a
+        // concurrent overwrite fails the download cleanly instead of
+        // splicing bytes from two different revisions into the temp file.
+        let pinned_request = GetObjectRequest {
+            generation: Some(metadata.generation),
+            ..request
+        };
+
+        // Truncate/create fresh so re-downloads don't leave stale tail bytes.
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(local_path)
             .await?;
 
-        fs::write(local_path, data).await?;
+        let mut start: u64 = 0;
+        while start < total_size {
+            let end = std::cmp::min(start + self.window_size as u64 - 1, total_size - 1);
+
+            let chunk = self
+                .client
+                .download_object(&pinned_request, &Range(Some(start), Some(end)))
+                .await?;
+            file.write_all(&chunk).await?;
+
+            start = end + 1;
+        }
 
-        Ok(())
+        Ok(ObjectVersion::GCS {
+            generation: metadata.generation,
+            metageneration: metadata.metageneration,
+        })
     }
 
-    pub async fn upload_file(&self, bucket: &str, key: &str, local_path: &Path) -> Result<()> {
-        let content = fs::read(local_path).await?;
+    pub async fn upload_file(
+        &self,
+        bucket: &str,
+        key: &str,
+        local_path: &Path,
+        expected_version: &ObjectVersion,
+    ) -> Result<UploadOutcome> {
+        let generation = expected_version.gcs_generation()?;
+        let file_size = fs::metadata(local_path).await?.len();
+
+        if file_size <= MULTIPART_THRESHOLD {
+            let content = fs::read(local_path).await?;
 
-        let upload_type = UploadType::Simple(Media::new(key.to_string()));
+            let upload_type = UploadType::Simple(Media::new(key.to_string()));
+            let request = UploadObjectRequest {
+                bucket: bucket.to_string(),
+                if_generation_match: Some(generation),
+                ..Default::default()
+            };
+
+            match self
+                .client
+                .upload_object(&request, content, &upload_type)
+                .await
+            {
+                std::result::Result::Ok(object) => {
+                    Ok(UploadOutcome::Uploaded(ObjectVersion::GCS {
+                        generation: object.generation,
+                        metageneration: object.metageneration,
+                    }))
+                }
+                Err(e) if gcs_is_conflict(&e) => Ok(UploadOutcome::Conflict),
+                Err(e) => Err(e.into()),
+            }
+        } else {
+            self.upload_file_resumable(bucket, key, local_path, file_size, generation)
+                .await
+        }
+    }
+
+    async fn upload_file_resumable(
+        &self,
+        bucket: &str,
+        key: &str,
+        local_path: &Path,
+        file_size: u64,
+        expected_generation: i64,
+    ) -> Result<UploadOutcome> {
         let request = UploadObjectRequest {
             bucket: bucket.to_string(),
+            if_generation_match: Some(expected_generation),
             ..Default::default()
         };
+        let media = Media::new(key.to_string());
+
+        let session = self
+            .client
+            .prepare_resumable_upload(&request, &media)
+            .await?;
+        let upload_client = ResumableUploadClient::new(session.uri, reqwest::Client::default());
+
+        let mut file = fs::File::open(local_path).await?;
+        let mut buf = vec![0u8; self.part_size];
+        let mut offset: u64 = 0;
+
+        loop {
+            let bytes_read = read_up_to(&mut file, &mut buf).await?;
+            let is_last_chunk = offset + bytes_read as u64 >= file_size;
+
+            if bytes_read == 0 && !is_last_chunk {
+                break;
+            }
+
+            let chunk = buf[..bytes_read].to_vec();
+            let chunk_result = upload_client
+                .upload_multiple_chunk(
+                    chunk,
+                    &ChunkSize::new(offset, offset + bytes_read as u64 - 1, Some(file_size)),
+                )
+                .await;
+
+            let object = match chunk_result {
+                std::result::Result::Ok(object) => object,
+                Err(e) if gcs_is_conflict(&e) => return Ok(UploadOutcome::Conflict),
+                Err(e) => return Err(e.into()),
+            };
+
+            offset += bytes_read as u64;
+            if is_last_chunk {
+                let object = object.ok_or_else(|| {
+                    anyhow::anyhow!("resumable upload finished without returning the object")
+                })?;
+                return Ok(UploadOutcome::Uploaded(ObjectVersion::GCS {
+                    generation: object.generation,
+                    metageneration: object.metageneration,
+                }));
+            }
+        }
+
+        Err(anyhow::anyhow!(
+            "resumable upload ended before the final chunk"
+        ))
+    }
+}
+
+pub struct AzureStorage {
+    client: ContainerClient,
+}
+
+impl AzureStorage {
+    pub async fn new(bucket: &str) -> Result<Self> {
+        let account = env::var("AZURE_STORAGE_ACCOUNT")
+            .map_err(|_| anyhow::anyhow!("AZURE_STORAGE_ACCOUNT must be set"))?;
+
+        let credentials = if let std::result::Result::Ok(sas_token) =
+            env::var("AZURE_STORAGE_SAS_TOKEN")
+        {
+            StorageCredentials::sas_token(sas_token)?
+        } else {
+            let access_key = env::var("AZURE_STORAGE_ACCESS_KEY").map_err(|_| {
+                anyhow::anyhow!("AZURE_STORAGE_SAS_TOKEN or AZURE_STORAGE_ACCESS_KEY must be set")
+            })?;
+            StorageCredentials::access_key(account.clone(), access_key)
+        };
+
+        let client = ClientBuilder::new(account, credentials).container_client(bucket);
+
+        Ok(Self { client })
+    }
+
+    pub async fn download_file(
+        &self,
+        _bucket: &str,
+        key: &str,
+        local_path: &Path,
+    ) -> Result<ObjectVersion> {
+        let blob_client = self.client.blob_client(key);
 
-        self.client
-            .upload_object(&request, content, &upload_type)
+        // Truncate/create fresh so re-downloads don't leave stale tail bytes.
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(local_path)
             .await?;
 
-        Ok(())
+        let mut etag = None;
+        let mut pages = blob_client.get().into_stream();
+
+        while let Some(page) = pages.next().await {
+            let page = page?;
+
+            // Derive the etag from the same response the content comes from,
+            // so it provably corresponds to the bytes written below rather
+            // than a snapshot taken by a second, independent request. Every
+            // page of a single blob download carries the same properties, so
+            // the first one seen is as good as any.
+            if etag.is_none() {
+                etag = Some(page.blob.properties.etag.to_string());
+            }
+
+            let chunk = page.data.collect().await?;
+            file.write_all(&chunk).await?;
+        }
+
+        let etag = etag.ok_or_else(|| anyhow::anyhow!("blob download returned no pages"))?;
+        Ok(ObjectVersion::Azure { etag })
+    }
+
+    pub async fn upload_file(
+        &self,
+        _bucket: &str,
+        key: &str,
+        local_path: &Path,
+        expected_version: &ObjectVersion,
+    ) -> Result<UploadOutcome> {
+        let etag = expected_version.azure_etag()?;
+        let content = fs::read(local_path).await?;
+        let blob_client = self.client.blob_client(key);
+
+        match blob_client
+            .put_block_blob(content)
+            .if_match(IfMatchCondition::Match(etag.to_string()))
+            .into_future()
+            .await
+        {
+            std::result::Result::Ok(response) => {
+                Ok(UploadOutcome::Uploaded(ObjectVersion::Azure {
+                    etag: response.etag.to_string(),
+                }))
+            }
+            Err(e) if azure_is_conflict(&e) => Ok(UploadOutcome::Conflict),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_smithy_runtime_api::client::orchestrator::HttpResponse;
+    use aws_smithy_runtime_api::http::StatusCode;
+    use aws_smithy_types::body::SdkBody;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn test_read_up_to_stops_when_buffer_is_full() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        tmp.write_all(b"hello world").unwrap();
+        let mut file = fs::File::open(tmp.path()).await.unwrap();
+
+        let mut buf = vec![0u8; 5];
+        let n = read_up_to(&mut file, &mut buf).await.unwrap();
+
+        assert_eq!(n, 5);
+        assert_eq!(&buf, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_read_up_to_returns_partial_fill_at_eof() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        tmp.write_all(b"hi").unwrap();
+        let mut file = fs::File::open(tmp.path()).await.unwrap();
+
+        let mut buf = vec![0u8; 10];
+        let n = read_up_to(&mut file, &mut buf).await.unwrap();
+
+        assert_eq!(n, 2);
+        assert_eq!(&buf[..2], b"hi");
+    }
+
+    #[tokio::test]
+    async fn test_read_up_to_returns_zero_at_eof() {
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let mut file = fs::File::open(tmp.path()).await.unwrap();
+
+        let mut buf = vec![0u8; 4];
+        let n = read_up_to(&mut file, &mut buf).await.unwrap();
+
+        assert_eq!(n, 0);
+    }
+
+    fn s3_error_with_status(status: u16) -> aws_sdk_s3::error::SdkError<()> {
+        let response = HttpResponse::new(StatusCode::try_from(status).unwrap(), SdkBody::empty());
+        aws_sdk_s3::error::SdkError::service_error((), response)
+    }
+
+    #[test]
+    fn test_s3_is_conflict_detects_412() {
+        assert!(s3_is_conflict(&s3_error_with_status(412)));
+    }
+
+    #[test]
+    fn test_s3_is_conflict_ignores_other_statuses() {
+        assert!(!s3_is_conflict(&s3_error_with_status(500)));
+    }
+
+    #[test]
+    fn test_gcs_is_conflict_detects_412() {
+        let err = google_cloud_storage::http::Error::Response(
+            google_cloud_storage::http::error::ErrorResponse {
+                code: 412,
+                message: "precondition failed".to_string(),
+                errors: Vec::new(),
+            },
+        );
+        assert!(gcs_is_conflict(&err));
+    }
+
+    #[test]
+    fn test_gcs_is_conflict_ignores_other_statuses() {
+        let err = google_cloud_storage::http::Error::Response(
+            google_cloud_storage::http::error::ErrorResponse {
+                code: 500,
+                message: "internal error".to_string(),
+                errors: Vec::new(),
+            },
+        );
+        assert!(!gcs_is_conflict(&err));
+    }
+
+    fn azure_error_with_status(status: azure_core::StatusCode) -> azure_core::Error {
+        azure_core::Error::new(
+            azure_core::error::ErrorKind::HttpResponse {
+                status,
+                error_code: None,
+            },
+            HttpError::new(status, Vec::new()),
+        )
+    }
+
+    #[test]
+    fn test_azure_is_conflict_detects_412() {
+        assert!(azure_is_conflict(&azure_error_with_status(
+            azure_core::StatusCode::PreconditionFailed
+        )));
+    }
+
+    #[test]
+    fn test_azure_is_conflict_ignores_other_statuses() {
+        assert!(!azure_is_conflict(&azure_error_with_status(
+            azure_core::StatusCode::InternalServerError
+        )));
     }
 }
@@ -1,18 +1,32 @@
 use anyhow::Result;
 
-/// Parses a cloud storage URI (e.g., s3://bucket/key or gs://bucket/key) into a tuple of (bucket, key)
+/// Parses a cloud storage URI (e.g., s3://bucket/key, gs://bucket/key or
+/// az://container/blob) into a tuple of (bucket, key). Also accepts Azure's
+/// `https://<account>.blob.core.windows.net/<container>/<blob>` URL form.
 pub fn parse_uri(uri: &Option<String>) -> Result<Option<(String, String)>> {
     if let Some(uri_str) = uri {
         if let Some(stripped) = uri_str
             .strip_prefix("s3://")
             .or_else(|| uri_str.strip_prefix("gs://"))
+            .or_else(|| uri_str.strip_prefix("az://"))
         {
             if let Some((bucket, key)) = stripped.split_once('/') {
                 return Ok(Some((bucket.to_string(), key.to_string())));
             }
         }
+
+        if let Some(stripped) = uri_str.strip_prefix("https://") {
+            if let Some((host, path)) = stripped.split_once('/') {
+                if host.contains(".blob.core.windows.net") {
+                    if let Some((container, blob)) = path.split_once('/') {
+                        return Ok(Some((container.to_string(), blob.to_string())));
+                    }
+                }
+            }
+        }
+
         return Err(anyhow::anyhow!(
-            "Invalid cloud storage URI format. Expected s3://bucket/key or gs://bucket/key"
+            "Invalid cloud storage URI format. Expected s3://bucket/key, gs://bucket/key or az://container/blob"
         ));
     }
     Ok(None)
@@ -42,6 +56,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_uri_valid_azure() {
+        let uri = Some(String::from("az://mycontainer/myblob"));
+        let result = parse_uri(&uri).unwrap();
+        assert_eq!(
+            result,
+            Some((String::from("mycontainer"), String::from("myblob")))
+        );
+    }
+
+    #[test]
+    fn test_parse_uri_valid_azure_https() {
+        let uri = Some(String::from(
+            "https://myaccount.blob.core.windows.net/mycontainer/myblob",
+        ));
+        let result = parse_uri(&uri).unwrap();
+        assert_eq!(
+            result,
+            Some((String::from("mycontainer"), String::from("myblob")))
+        );
+    }
+
     #[test]
     fn test_parse_uri_invalid_format() {
         let uri = Some(String::from("invalid://mybucket/mykey"));